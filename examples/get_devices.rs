@@ -1,7 +1,22 @@
-use livekit_gstreamer::{get_devices_info, MediaDeviceInfo};
+use livekit_gstreamer::{get_devices_info, get_windows_info, GstMediaDevice, MediaDeviceInfo};
 
 fn main() {
     gstreamer::init().unwrap();
+
+    // `cargo run --example get_devices -- --caps /dev/video0` dumps the raw caps of a device,
+    // which is what to attach to a "device not supported" report
+    let args: Vec<String> = std::env::args().collect();
+    if let [_, flag, path] = args.as_slice() {
+        if flag == "--caps" {
+            match GstMediaDevice::from_device_path(path).map(|device| device.caps_string()) {
+                Ok(Some(caps)) => println!("{}", caps),
+                Ok(None) => println!("{} reports no caps", path),
+                Err(e) => eprintln!("{}", e),
+            }
+            return;
+        }
+    }
+
     let devices = get_devices_info();
 
     let (video_devices, audio_devices): (Vec<MediaDeviceInfo>, Vec<MediaDeviceInfo>) = devices
@@ -10,33 +25,20 @@ fn main() {
 
     println!("Video Devices:");
     for device_info in video_devices {
-        println!(
-            "============== Media Device Info ({:?}|{:?}) ==============",
-            device_info.display_name, device_info.device_class
-        );
-        println!("Device path: {}", device_info.device_path);
-        println!("Device name: {}", device_info.display_name);
-        println!("Device class: {}", device_info.device_class);
-        println!("Capabilities:");
-        for capability in device_info.capabilities {
-            println!("  {:?}", capability);
-        }
-        println!("============== End Media Device Info ==============");
+        println!("{}", device_info);
     }
     println!("\n------------------------------------------------------\n");
     println!("Audio Devices:");
     for device_info in audio_devices {
-        println!(
-            "============== Media Device Info ({:?}|{:?}) ==============",
-            device_info.display_name, device_info.device_class
-        );
-        println!("Device path: {}", device_info.device_path);
-        println!("Device name: {}", device_info.display_name);
-        println!("Device class: {}", device_info.device_class);
-        println!("Capabilities:");
-        for capability in device_info.capabilities {
-            println!("  {:?}", capability);
+        println!("{}", device_info);
+    }
+
+    let windows = get_windows_info();
+    if !windows.is_empty() {
+        println!("\n------------------------------------------------------\n");
+        println!("Windows:");
+        for window_info in windows {
+            println!("{}", window_info);
         }
-        println!("============== End Media Device Info ==============");
     }
 }
@@ -0,0 +1,99 @@
+use dotenvy::dotenv;
+use livekit::{Room, RoomEvent, RoomOptions};
+
+use livekit_api::access_token;
+use livekit_gstreamer::{
+    AudioPublishOptions, GstMediaStream, LKParticipant, LKParticipantError, PublishOptions,
+    ScreenPublishOptions, StreamGroup, VideoPublishOptions,
+};
+use std::{env, sync::Arc};
+
+#[tokio::main]
+async fn main() -> Result<(), LKParticipantError> {
+    dotenv().ok();
+    // Initialize gstreamer
+    gstreamer::init().unwrap();
+    std::env::set_var("RUST_LOG", "info");
+    env_logger::init();
+
+    let url = env::var("LIVEKIT_URL").expect("LIVEKIT_URL is not set");
+    let api_key = env::var("LIVEKIT_API_KEY").expect("LIVEKIT_API_KEY is not set");
+    let api_secret = env::var("LIVEKIT_API_SECRET").expect("LIVEKIT_API_SECRET is not set");
+
+    let token = access_token::AccessToken::with_api_key(&api_key, &api_secret)
+        .with_identity("rust-bot-present")
+        .with_name("Rust Bot Present")
+        .with_grants(access_token::VideoGrants {
+            room_join: true,
+            room: "DemoRoom".to_string(),
+            ..Default::default()
+        })
+        .to_jwt()
+        .unwrap();
+
+    let (room, mut room_rx) = Room::connect(&url, &token, RoomOptions::default())
+        .await
+        .unwrap();
+
+    let new_room = Arc::new(room);
+    log::info!(
+        "Connected to room: {} - {}",
+        new_room.name(),
+        String::from(new_room.sid().await)
+    );
+
+    // The primary screen, published as a screen share track
+    let screen = GstMediaStream::new(PublishOptions::Screen(
+        ScreenPublishOptions::builder()
+            .framerate(15)
+            .show_cursor(true)
+            .build()?,
+    ));
+
+    // Note: Make sure to replace the device_id with the correct device and the codecs and resolutions are supported by the device
+    // This can be checked by running `v4l2-ctl --list-formats-ext -d /dev/video0` for example or using gst-device-monitor-1.0 Video/Source
+    let camera = GstMediaStream::new(PublishOptions::Video(
+        VideoPublishOptions::builder("/dev/video0")
+            .codec("image/jpeg")
+            .build()?,
+    ));
+
+    let mic = GstMediaStream::new(PublishOptions::Audio(
+        AudioPublishOptions::builder("hw:2").build()?,
+    ));
+
+    // Started together, so a missing device fails before anything is published
+    let mut streams = StreamGroup::new(vec![screen, camera, mic]);
+    streams.start_all().await.unwrap();
+
+    let mut participant = LKParticipant::new(new_room.clone());
+    for stream in streams.streams_mut() {
+        participant.publish_stream(stream, None).await?;
+        log::info!(
+            "Published {} stream from device: {}",
+            stream.kind(),
+            stream.get_device_name().unwrap()
+        );
+    }
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                log::info!("Stopping the presentation");
+                break;
+            }
+            msg = room_rx.recv() => match msg {
+                Some(RoomEvent::Disconnected { reason }) => {
+                    log::info!("Disconnected from room: {:?}", reason);
+                    break;
+                }
+                Some(msg) => log::info!("Received room event: {:?}", msg),
+                None => break,
+            },
+        }
+    }
+
+    participant.shutdown_all(streams.streams_mut()).await?;
+
+    Ok(())
+}
@@ -36,13 +36,10 @@ async fn main() -> Result<(), LKParticipantError> {
 
     let new_room = Arc::new(room);
 
-    let publish_options = AudioPublishOptions {
-        codec: "audio/x-raw".to_string(),
-        device_id: "front:3".to_string(),
-        framerate: 32000,
-        channels: 2,
-        selected_channel: None,
-    };
+    let publish_options = AudioPublishOptions::builder("front:3")
+        .framerate(32000)
+        .channels(2)
+        .build()?;
 
     let mut stream = GstMediaStream::new(PublishOptions::Audio(publish_options));
 
@@ -0,0 +1,74 @@
+use dotenvy::dotenv;
+use livekit::{Room, RoomEvent};
+use livekit_gstreamer::{
+    e2ee_room_options, AudioPublishOptions, GstMediaStream, LKParticipant, LKParticipantError,
+    PublishOptions,
+};
+
+use livekit_api::access_token;
+use std::{env, sync::Arc};
+
+#[tokio::main]
+async fn main() -> Result<(), LKParticipantError> {
+    dotenv().ok();
+    // Initialize gstreamer
+    gstreamer::init().unwrap();
+    std::env::set_var("RUST_LOG", "info");
+    env_logger::init();
+
+    let url = env::var("LIVEKIT_URL").expect("LIVEKIT_URL is not set");
+    let api_key = env::var("LIVEKIT_API_KEY").expect("LIVEKIT_API_KEY is not set");
+    let api_secret = env::var("LIVEKIT_API_SECRET").expect("LIVEKIT_API_SECRET is not set");
+    // The shared key has to be handed to every subscriber out of band
+    let e2ee_key = env::var("LIVEKIT_E2EE_KEY").expect("LIVEKIT_E2EE_KEY is not set");
+
+    let token = access_token::AccessToken::with_api_key(&api_key, &api_secret)
+        .with_identity("rust-bot-microphone-e2ee")
+        .with_name("Rust Bot Microphone (E2EE)")
+        .with_grants(access_token::VideoGrants {
+            room_join: true,
+            room: "DemoRoom".to_string(),
+            ..Default::default()
+        })
+        .to_jwt()
+        .unwrap();
+
+    let (room, mut room_rx) = Room::connect(&url, &token, e2ee_room_options(e2ee_key.into_bytes()))
+        .await
+        .unwrap();
+
+    let new_room = Arc::new(room);
+
+    let publish_options = AudioPublishOptions::builder("front:3")
+        .framerate(32000)
+        .channels(2)
+        .build()?;
+
+    let mut stream = GstMediaStream::new(PublishOptions::Audio(publish_options));
+
+    stream.start().await?;
+
+    let mut participant = LKParticipant::with_e2ee(new_room.clone())?;
+    participant.publish_stream(&mut stream, None).await?;
+
+    log::info!(
+        "Connected to room: {} - {}",
+        new_room.name(),
+        String::from(new_room.sid().await)
+    );
+
+    while let Some(msg) = room_rx.recv().await {
+        match msg {
+            RoomEvent::Disconnected { reason } => {
+                log::info!("Disconnected from room: {:?}", reason);
+                stream.stop().await?;
+                break;
+            }
+            _ => {
+                log::info!("Received room event: {:?}", msg);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -4,7 +4,7 @@ use livekit::{Room, RoomEvent, RoomOptions};
 use livekit_api::access_token;
 use livekit_gstreamer::{
     AudioPublishOptions, GstMediaStream, LKParticipant, LKParticipantError, PublishOptions,
-    VideoPublishOptions,
+    StreamGroup, VideoPublishOptions,
 };
 use std::{env, sync::Arc};
 
@@ -44,79 +44,53 @@ async fn main() -> Result<(), LKParticipantError> {
 
     // Note: Make sure to replace the device_id with the correct device and the codecs and resolutions are supported by the device
     // This can be checked by running `v4l2-ctl --list-formats-ext -d /dev/video0` for example or using gst-device-monitor-1.0 Video/Source
-    let mut stream1 = GstMediaStream::new(PublishOptions::Video(VideoPublishOptions {
-        codec: "image/jpeg".to_string(),
-        width: 1920,
-        height: 1080,
-        framerate: 30,
-        device_id: "/dev/video0".to_string(),
-    }));
-
-    let mut stream2 = GstMediaStream::new(PublishOptions::Video(VideoPublishOptions {
-        codec: "video/x-h264".to_string(),
-        width: 1280,
-        height: 720,
-        framerate: 30,
-        device_id: "/dev/video4".to_string(),
-    }));
-
-    let mut stream3 = GstMediaStream::new(PublishOptions::Audio(AudioPublishOptions {
-        codec: "audio/x-raw".to_string(),
-        device_id: "front:3".to_string(),
-        framerate: 32000,
-        channels: 2,
-        selected_channel: None,
-    }));
-
-    let mut stream4 = GstMediaStream::new(PublishOptions::Audio(AudioPublishOptions {
-        codec: "audio/x-raw".to_string(),
-        device_id: "hw:2".to_string(),
-        framerate: 48000,
-        channels: 1,
-        selected_channel: None,
-    }));
-
-    stream1.start().await.unwrap();
-    stream2.start().await.unwrap();
-    stream3.start().await.unwrap();
-    stream4.start().await.unwrap();
+    let stream1 = GstMediaStream::new(PublishOptions::Video(
+        VideoPublishOptions::builder("/dev/video0")
+            .codec("image/jpeg")
+            .resolution(1920, 1080)
+            .build()?,
+    ));
+
+    let stream2 = GstMediaStream::new(PublishOptions::Video(
+        VideoPublishOptions::builder("/dev/video4")
+            .codec("video/x-h264")
+            .resolution(1280, 720)
+            .build()?,
+    ));
+
+    let stream3 = GstMediaStream::new(PublishOptions::Audio(
+        AudioPublishOptions::builder("front:3")
+            .framerate(32000)
+            .channels(2)
+            .build()?,
+    ));
+
+    let stream4 = GstMediaStream::new(PublishOptions::Audio(
+        AudioPublishOptions::builder("hw:2").build()?,
+    ));
+
+    let mut streams = StreamGroup::new(vec![stream1, stream2, stream3, stream4]);
+    streams.start_all().await.unwrap();
 
     let mut participant = LKParticipant::new(new_room.clone());
-    participant.publish_stream(&mut stream1, None).await?;
-    log::info!(
-        "Published {} stream from device: {}",
-        stream1.kind(),
-        stream1.get_device_name().unwrap()
-    );
-
-    participant.publish_stream(&mut stream2, None).await?;
-    log::info!(
-        "Published {} stream from device: {}",
-        stream2.kind(),
-        stream2.get_device_name().unwrap()
-    );
-    participant.publish_stream(&mut stream3, None).await?;
-    log::info!(
-        "Published {} stream from device: {}",
-        stream3.kind(),
-        stream3.get_device_name().unwrap()
-    );
-
-    participant.publish_stream(&mut stream4, None).await?;
-    log::info!(
-        "Published {} stream from device: {}",
-        stream4.kind(),
-        stream4.get_device_name().unwrap()
-    );
+    for stream in streams.streams_mut() {
+        participant.publish_stream(stream, None).await?;
+        log::info!(
+            "Published {} stream from device: {}",
+            stream.kind(),
+            stream.get_device_name().unwrap()
+        );
+    }
 
     while let Some(msg) = room_rx.recv().await {
         match msg {
             RoomEvent::Disconnected { reason } => {
                 log::info!("Disconnected from room: {:?}", reason);
-                stream1.stop().await?;
-                stream2.stop().await?;
-                stream3.stop().await?;
-                stream4.stop().await?;
+                participant.shutdown_all(streams.streams_mut()).await?;
+                // e.g. another process joined with the "rust-bot-multitrack" identity
+                if let Some(err) = participant.disconnect_error(reason) {
+                    return Err(err);
+                }
                 break;
             }
             _ => {
@@ -0,0 +1,76 @@
+use dotenvy::dotenv;
+use livekit::{Room, RoomEvent, RoomOptions};
+
+use livekit_api::access_token;
+use livekit_gstreamer::{
+    supported_video_codecs, GstMediaStream, LKParticipant, LKParticipantError, PublishOptions,
+    ScreenPublishOptions, VideoCodec,
+};
+use std::{env, sync::Arc};
+
+#[tokio::main]
+async fn main() -> Result<(), LKParticipantError> {
+    dotenv().ok();
+    // Initialize gstreamer
+    gstreamer::init().unwrap();
+    std::env::set_var("RUST_LOG", "info");
+    env_logger::init();
+
+    let url = env::var("LIVEKIT_URL").expect("LIVEKIT_URL is not set");
+    let api_key = env::var("LIVEKIT_API_KEY").expect("LIVEKIT_API_KEY is not set");
+    let api_secret = env::var("LIVEKIT_API_SECRET").expect("LIVEKIT_API_SECRET is not set");
+
+    let token = access_token::AccessToken::with_api_key(&api_key, &api_secret)
+        .with_identity("rust-bot-screen")
+        .with_name("Rust Bot Screen")
+        .with_grants(access_token::VideoGrants {
+            room_join: true,
+            room: "DemoRoom".to_string(),
+            ..Default::default()
+        })
+        .to_jwt()
+        .unwrap();
+
+    let (room, mut room_rx) = Room::connect(&url, &token, RoomOptions::default())
+        .await
+        .unwrap();
+
+    let new_room = Arc::new(room);
+
+    // AV1 keeps text sharp at low bitrates, fall back to LiveKit's default codec without it
+    let mut options = ScreenPublishOptions::builder()
+        .framerate(15)
+        .show_cursor(true);
+    if supported_video_codecs().contains(&VideoCodec::AV1) {
+        options = options.preferred_codec(VideoCodec::AV1);
+    }
+    let mut stream = GstMediaStream::new(PublishOptions::Screen(options.build()?));
+
+    stream.start().await.unwrap();
+
+    let mut participant = LKParticipant::new(new_room.clone());
+
+    // Published as a screen share, at the resolution the screen capture negotiated
+    participant.publish_stream(&mut stream, None).await?;
+
+    log::info!(
+        "Connected to room: {} - {}",
+        new_room.name(),
+        String::from(new_room.sid().await)
+    );
+
+    while let Some(msg) = room_rx.recv().await {
+        match msg {
+            RoomEvent::Disconnected { reason } => {
+                log::info!("Disconnected from room: {:?}", reason);
+                stream.stop().await?;
+                break;
+            }
+            _ => {
+                log::info!("Received room event: {:?}", msg);
+            }
+        }
+    }
+
+    Ok(())
+}
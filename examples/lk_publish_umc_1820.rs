@@ -36,21 +36,17 @@ async fn main() -> Result<(), LKParticipantError> {
 
     let new_room = Arc::new(room);
 
-    let publish_options1 = AudioPublishOptions {
-        codec: "audio/x-raw".to_string(),
-        device_id: "hw:4".to_string(),
-        framerate: 96000,
-        channels: 10,
-        selected_channel: Some(1),
-    };
+    let publish_options1 = AudioPublishOptions::builder("hw:4")
+        .framerate(96000)
+        .channels(10)
+        .selected_channel(1)
+        .build()?;
 
-    let publish_options2 = AudioPublishOptions {
-        codec: "audio/x-raw".to_string(),
-        device_id: "hw:4".to_string(),
-        framerate: 96000,
-        channels: 10,
-        selected_channel: Some(2),
-    };
+    let publish_options2 = AudioPublishOptions::builder("hw:4")
+        .framerate(96000)
+        .channels(10)
+        .selected_channel(2)
+        .build()?;
 
     let mut stream1 = GstMediaStream::new(PublishOptions::Audio(publish_options1));
 
@@ -6,13 +6,9 @@ async fn main() -> Result<(), GStreamerError> {
         GStreamerError::PipelineError(format!("Failed to initialize gstreamer: {}", e))
     })?;
 
-    let publish_options = AudioPublishOptions {
-        codec: "audio/x-raw".to_string(),
-        device_id: "hw:2".to_string(),
-        framerate: 32000,
-        channels: 1,
-        selected_channel: None,
-    };
+    let publish_options = AudioPublishOptions::builder("hw:2")
+        .framerate(32000)
+        .build()?;
 
     let mut stream = GstMediaStream::new(PublishOptions::Audio(publish_options));
 
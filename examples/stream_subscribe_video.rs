@@ -9,13 +9,12 @@ async fn main() -> Result<(), GStreamerError> {
 
     // Note: Make sure to replace the device_id with the correct device and the codecs and resolutions are supported by the device
     // This can be checked by running `v4l2-ctl --list-formats-ext -d /dev/video0` for example or using gst-device-monitor-1.0 Video/Source
-    let mut stream = GstMediaStream::new(PublishOptions::Video(VideoPublishOptions {
-        codec: "image/jpeg".to_string(),
-        width: 1920,
-        height: 1080,
-        framerate: 30,
-        device_id: "/dev/video0".to_string(),
-    }));
+    let mut stream = GstMediaStream::new(PublishOptions::Video(
+        VideoPublishOptions::builder("/dev/video0")
+            .codec("image/jpeg")
+            .resolution(1920, 1080)
+            .build()?,
+    ));
 
     stream.start().await.unwrap();
 
@@ -0,0 +1,97 @@
+use crate::media_device::GStreamerError;
+
+const ASOUND_CARDS_PATH: &str = "/proc/asound/cards";
+
+/// A sound card known to ALSA. `index` is the `N` of `hw:N`, which can change between boots,
+/// while `id` (e.g. "U192k") and `name` (e.g. "UMC1820") are stable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlsaCard {
+    pub index: u32,
+    pub id: String,
+    pub name: String,
+}
+
+pub fn list_alsa_cards() -> Result<Vec<AlsaCard>, GStreamerError> {
+    let cards = std::fs::read_to_string(ASOUND_CARDS_PATH).map_err(|e| {
+        GStreamerError::DeviceError(format!("Failed to read {}: {}", ASOUND_CARDS_PATH, e))
+    })?;
+    Ok(parse_cards(&cards))
+}
+
+/// Parses `/proc/asound/cards`, where each card is listed as
+/// ` 1 [U192k          ]: USB-Audio - UMC1820` followed by a description line
+fn parse_cards(cards: &str) -> Vec<AlsaCard> {
+    cards
+        .lines()
+        .filter_map(|line| {
+            let (index, rest) = line.trim_start().split_once(' ')?;
+            let index = index.parse().ok()?;
+            let (id, rest) = rest.trim_start().strip_prefix('[')?.split_once(']')?;
+            let name = rest
+                .trim_start_matches(':')
+                .split_once(" - ")
+                .map_or(rest, |(_, name)| name);
+            Some(AlsaCard {
+                index,
+                id: id.trim().to_string(),
+                name: name.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Replaces a card name or id in an ALSA device id by the card's current index, so that
+/// "UMC1820" resolves to "hw:1" and "front:UMC1820" to "front:1". Ids that do not name
+/// a card, e.g. "hw:1" or "/dev/video0", are returned unchanged.
+pub fn resolve_device_id(device_id: &str, cards: &[AlsaCard]) -> String {
+    let (prefix, card) = device_id.split_once(':').unwrap_or(("hw", device_id));
+    cards
+        .iter()
+        .find(|c| c.name.eq_ignore_ascii_case(card) || c.id.eq_ignore_ascii_case(card))
+        .map_or_else(
+            || device_id.to_string(),
+            |c| format!("{}:{}", prefix, c.index),
+        )
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[cfg(test)]
+    const CARDS: &str = " 0 [PCH            ]: HDA-Intel - HDA Intel PCH
+                      HDA Intel PCH at 0xf7f10000 irq 32
+ 1 [U192k          ]: USB-Audio - UMC1820
+                      BEHRINGER UMC1820 at usb-0000:00:14.0-1, high speed
+";
+
+    #[test]
+    fn test_parse_cards() {
+        let cards = parse_cards(CARDS);
+        assert_eq!(
+            cards,
+            vec![
+                AlsaCard {
+                    index: 0,
+                    id: "PCH".to_string(),
+                    name: "HDA Intel PCH".to_string(),
+                },
+                AlsaCard {
+                    index: 1,
+                    id: "U192k".to_string(),
+                    name: "UMC1820".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_device_id() {
+        let cards = parse_cards(CARDS);
+        assert_eq!(resolve_device_id("UMC1820", &cards), "hw:1");
+        assert_eq!(resolve_device_id("front:umc1820", &cards), "front:1");
+        assert_eq!(resolve_device_id("hw:U192k", &cards), "hw:1");
+        assert_eq!(resolve_device_id("hw:4", &cards), "hw:4");
+        assert_eq!(resolve_device_id("/dev/video0", &cards), "/dev/video0");
+    }
+}
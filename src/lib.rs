@@ -1,8 +1,18 @@
+#[cfg(target_os = "linux")]
+pub(crate) mod alsa;
 pub mod lk_participant;
 pub mod media_device;
 pub mod media_stream;
 pub(crate) mod utils;
+#[cfg(target_os = "linux")]
+pub(crate) mod v4l2;
+#[cfg(target_os = "linux")]
+pub(crate) mod x11;
 
+#[cfg(target_os = "linux")]
+pub use alsa::{list_alsa_cards, AlsaCard};
 pub use lk_participant::*;
 pub use media_device::*;
 pub use media_stream::*;
+#[cfg(target_os = "linux")]
+pub use v4l2::VideoInput;
@@ -1,22 +1,315 @@
-use crate::media_device::GStreamerError;
-use crate::media_stream::{GstMediaStream, PublishOptions};
+use crate::media_device::{GStreamerError, DECKLINK_AUDIO_RATE};
+use crate::media_stream::{
+    GstMediaStream, NegotiatedCaps, PublishOptions, StreamStats, VideoCodec,
+};
 use crate::utils::random_string;
 use gstreamer::Buffer;
+use livekit::e2ee::key_provider::{KeyProvider, KeyProviderOptions};
+use livekit::e2ee::{E2eeOptions, EncryptionType};
 use livekit::options::TrackPublishOptions;
+use livekit::proto::DisconnectReason;
 use livekit::track::{LocalAudioTrack, LocalTrack, LocalVideoTrack, TrackSource};
 use livekit::webrtc::audio_source::native::NativeAudioSource;
+use livekit::webrtc::peer_connection_factory::PeerConnectionFactory;
 use livekit::webrtc::prelude::{
     AudioFrame, I420Buffer, RtcAudioSource, RtcVideoSource, VideoFrame, VideoResolution,
     VideoRotation,
 };
 use livekit::webrtc::video_source::native::NativeVideoSource;
-use livekit::{Room, RoomError};
+use livekit::webrtc::MediaType;
+use livekit::{Room, RoomError, RoomEvent, RoomOptions};
+use once_cell::sync::Lazy;
+use serde::Serialize;
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use thiserror::Error;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
+
+/// The resolution to create the video source of a stream with: the size it negotiated, which
+/// can differ from the requested `width`x`height`, or the requested size until it is known
+fn source_resolution(
+    negotiated: Option<&NegotiatedCaps>,
+    width: i32,
+    height: i32,
+) -> VideoResolution {
+    let (width, height) = negotiated
+        .and_then(|caps| Some((caps.width?, caps.height?)))
+        .unwrap_or((width, height));
+    VideoResolution {
+        width: width as u32,
+        height: height as u32,
+    }
+}
+
+/// Copies `rows` rows of `row_size` bytes of a plane at `offset` in `src`, whose rows are
+/// `src_stride` bytes apart, into `dst` with rows `dst_stride` bytes apart. Returns false
+/// without copying if either is too small to hold the plane.
+fn copy_plane(
+    src: &[u8],
+    offset: usize,
+    src_stride: usize,
+    dst: &mut [u8],
+    dst_stride: usize,
+    row_size: usize,
+    rows: usize,
+) -> bool {
+    if rows == 0 {
+        return true;
+    }
+    let last_row = (rows - 1) * src_stride;
+    if src.len() < offset + last_row + row_size || dst.len() < (rows - 1) * dst_stride + row_size {
+        return false;
+    }
+    for row in 0..rows {
+        let src_start = offset + row * src_stride;
+        let dst_start = row * dst_stride;
+        dst[dst_start..dst_start + row_size].copy_from_slice(&src[src_start..src_start + row_size]);
+    }
+    true
+}
+
+/// The plane offsets and strides of the I420 frame in `frame`, from its video meta. Frames
+/// without one have the default GStreamer layout, whose rows are padded to 4 bytes, so odd
+/// widths are not tightly packed. `None` for an empty size.
+fn i420_layout(
+    frame: &gstreamer::BufferRef,
+    width: u32,
+    height: u32,
+) -> Option<([usize; 3], [usize; 3])> {
+    let (offsets, strides) = match frame.meta::<gstreamer_video::VideoMeta>() {
+        Some(meta) => (meta.offset().to_vec(), meta.stride().to_vec()),
+        None => {
+            let info = gstreamer_video::VideoInfo::builder(
+                gstreamer_video::VideoFormat::I420,
+                width,
+                height,
+            )
+            .build()
+            .ok()?;
+            (info.offset().to_vec(), info.stride().to_vec())
+        }
+    };
+    match (offsets.as_slice(), strides.as_slice()) {
+        ([y, u, v, ..], [stride_y, stride_u, stride_v, ..]) => Some((
+            [*y, *u, *v],
+            [*stride_y as usize, *stride_u as usize, *stride_v as usize],
+        )),
+        _ => None,
+    }
+}
+
+/// The samples per channel of an interleaved buffer of `samples` samples, `None` unless it
+/// holds a whole number of frames of `num_channels` channels
+fn samples_per_channel(samples: usize, num_channels: u32) -> Option<u32> {
+    let num_channels = num_channels as usize;
+    if num_channels == 0 || samples % num_channels != 0 {
+        return None;
+    }
+    Some((samples / num_channels) as u32)
+}
+
+/// Builds the participant metadata entry of a track, `None` if there is nothing to publish.
+/// Metadata that is not valid JSON is published as a string.
+fn track_metadata_entry(
+    track_name: &str,
+    metadata: Option<&str>,
+    channel: Option<i32>,
+) -> Option<serde_json::Value> {
+    if metadata.is_none() && channel.is_none() {
+        return None;
+    }
+    let metadata = metadata.map(|metadata| {
+        serde_json::from_str(metadata)
+            .unwrap_or_else(|_| serde_json::Value::String(metadata.to_string()))
+    });
+    Some(serde_json::json!({
+        "name": track_name,
+        "metadata": metadata,
+        "channel": channel,
+    }))
+}
+
+/// Returns `participant_metadata` with its `"tracks"` key set to `tracks`, keeping the other keys
+/// the application set. Empty metadata counts as an empty object; metadata that is not a JSON
+/// object is left alone and an error is returned, since it cannot hold the track entries.
+fn merge_track_metadata(
+    participant_metadata: &str,
+    tracks: serde_json::Map<String, serde_json::Value>,
+) -> Result<String, LKParticipantError> {
+    let mut metadata = if participant_metadata.is_empty() {
+        serde_json::Map::new()
+    } else {
+        match serde_json::from_str(participant_metadata) {
+            Ok(serde_json::Value::Object(metadata)) => metadata,
+            _ => {
+                return Err(LKParticipantError::StreamingError(
+                    "Participant metadata is not a JSON object, cannot add track metadata"
+                        .to_string(),
+                ))
+            }
+        }
+    };
+    metadata.insert("tracks".to_string(), serde_json::Value::Object(tracks));
+    Ok(serde_json::Value::Object(metadata).to_string())
+}
+
+/// Returns the video codecs the bundled libwebrtc can encode. The prebuilt libwebrtc of the
+/// LiveKit SDK ships VP8, VP9 and AV1 software encoders everywhere; H264 depends on the
+/// platform's hardware or OpenH264 support.
+pub fn supported_video_codecs() -> Vec<VideoCodec> {
+    static SUPPORTED: Lazy<Vec<VideoCodec>> = Lazy::new(|| {
+        let capabilities =
+            PeerConnectionFactory::default().get_rtp_sender_capabilities(MediaType::Video);
+        [
+            VideoCodec::VP8,
+            VideoCodec::VP9,
+            VideoCodec::H264,
+            VideoCodec::AV1,
+        ]
+        .into_iter()
+        .filter(|codec| {
+            let mime_type = format!("video/{}", codec_name(*codec));
+            capabilities
+                .codecs
+                .iter()
+                .any(|c| c.mime_type.eq_ignore_ascii_case(&mime_type))
+        })
+        .collect()
+    });
+    SUPPORTED.clone()
+}
+
+fn codec_name(codec: VideoCodec) -> &'static str {
+    match codec {
+        VideoCodec::VP8 => "VP8",
+        VideoCodec::VP9 => "VP9",
+        VideoCodec::H264 => "H264",
+        VideoCodec::AV1 => "AV1",
+    }
+}
+
+/// How a stream is published, computed from its publish options by
+/// `LKParticipant::publish_stream`
+enum TrackSettings {
+    Video {
+        source: TrackSource,
+        preferred_codec: Option<VideoCodec>,
+        width: i32,
+        height: i32,
+        framerate: i32,
+    },
+    Audio {
+        sample_rate: i32,
+        num_channels: i32,
+    },
+}
+
+/// Returns the options publishing a video track from `source`, encoded with `codec` if given
+fn video_publish_options(
+    source: TrackSource,
+    codec: Option<VideoCodec>,
+) -> Result<TrackPublishOptions, LKParticipantError> {
+    let mut options = TrackPublishOptions {
+        source,
+        ..Default::default()
+    };
+    if let Some(codec) = codec {
+        if !supported_video_codecs().contains(&codec) {
+            return Err(LKParticipantError::StreamingError(format!(
+                "Video codec {} is not supported by libwebrtc, supported codecs: {:?}",
+                codec_name(codec),
+                supported_video_codecs()
+            )));
+        }
+        options.video_codec = match codec {
+            VideoCodec::VP8 => livekit::options::VideoCodec::VP8,
+            VideoCodec::VP9 => livekit::options::VideoCodec::VP9,
+            VideoCodec::H264 => livekit::options::VideoCodec::H264,
+            VideoCodec::AV1 => livekit::options::VideoCodec::AV1,
+        };
+    }
+    Ok(options)
+}
+
+/// Returns a name unique to this machine and device, `<prefix>-<hostname>-<device id>`, e.g.
+/// "camera-lab-pc-dev-video0" for "/dev/video0", to derive participant identities and track
+/// names when several publisher processes (e.g. one per camera) join the same room.
+///
+/// LiveKit requires identities to be unique within a room: a participant joining with the
+/// identity of a connected one disconnects it with a `DuplicateIdentity` reason, so processes
+/// sharing a hard-coded identity silently kick each other out.
+pub fn host_scoped_name(prefix: &str, device_id: &str) -> String {
+    scoped_name(prefix, &hostname(), device_id)
+}
+
+fn scoped_name(prefix: &str, host: &str, device_id: &str) -> String {
+    [prefix, host, device_id]
+        .iter()
+        .map(|part| {
+            part.chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+                .collect::<String>()
+                .split('-')
+                .filter(|segment| !segment.is_empty())
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn hostname() -> String {
+    #[cfg(unix)]
+    {
+        let mut name = [0u8; 256];
+        let res = unsafe { libc::gethostname(name.as_mut_ptr() as *mut libc::c_char, name.len()) };
+        if res == 0 {
+            let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+            return String::from_utf8_lossy(&name[..len]).into_owned();
+        }
+    }
+    #[cfg(windows)]
+    if let Ok(name) = std::env::var("COMPUTERNAME") {
+        return name;
+    }
+    "unknown-host".to_string()
+}
+
+/// The largest gap between normalized timestamps, in frame intervals, that is kept as is, e.g.
+/// after frames were dropped. Larger gaps are closed to a single frame interval.
+const MAX_PACING_GAP_INTERVALS: i64 = 10;
+
+/// Maps buffer timestamps to the timestamps of published video frames
+#[derive(Debug, Clone, Copy)]
+struct FrameTimestamps {
+    offset_us: i64,
+    // The frame interval to keep between timestamps when they are normalized
+    pacing_interval_us: Option<i64>,
+    last_us: Option<i64>,
+}
+
+impl FrameTimestamps {
+    fn next(&mut self, pts_us: i64) -> i64 {
+        let mut timestamp_us = self.offset_us + pts_us;
+        if let (Some(interval_us), Some(last_us)) = (self.pacing_interval_us, self.last_us) {
+            let paced_us = last_us + interval_us;
+            if timestamp_us < paced_us {
+                // Stale frames of a burst are paced until the source catches up
+                timestamp_us = paced_us;
+            } else if timestamp_us > last_us + interval_us * MAX_PACING_GAP_INTERVALS {
+                // A jump ahead, e.g. after a pause, shifts the timestamps of the following
+                // frames too, so that they continue from the last published one
+                self.offset_us -= timestamp_us - paced_us;
+                timestamp_us = paced_us;
+            }
+        }
+        self.last_us = Some(timestamp_us);
+        timestamp_us
+    }
+}
 
 #[derive(Error, Debug)]
 pub enum LKParticipantError {
@@ -26,26 +319,262 @@ pub enum LKParticipantError {
     LivekitError(#[from] RoomError),
     #[error("Streaming error: {0}")]
     StreamingError(String),
+    /// Some tracks, or their metadata, could not be published again by
+    /// `LKParticipant::republish_all`
+    #[error("Republished {recovered:?}, failed to republish {failed:?}, track metadata error: {metadata_error:?}")]
+    RepublishError {
+        /// The names of the tracks that were published again
+        recovered: Vec<String>,
+        /// The names of the tracks that failed, each followed by its error
+        failed: Vec<String>,
+        /// Why the track metadata of the recovered tracks could not be published again
+        metadata_error: Option<String>,
+    },
+    /// Another connection joined the room with the identity of this participant, which the
+    /// server then disconnected, see `LKParticipant::disconnect_error`
+    #[error("Participant {0} was disconnected by another connection with the same identity, use host_scoped_name for unique identities")]
+    DuplicateIdentity(String),
+}
+
+/// Returns room options enabling end-to-end encryption with a key shared by all participants.
+///
+/// The key never reaches the LiveKit server: it has to be distributed to every participant
+/// out of band, e.g. by the application backend, and participants without it only receive
+/// undecodable media. Pass the options to `Room::connect` and the room to
+/// `LKParticipant::with_e2ee`.
+pub fn e2ee_room_options(shared_key: Vec<u8>) -> RoomOptions {
+    let key_provider = KeyProvider::with_shared_key(KeyProviderOptions::default(), shared_key);
+    RoomOptions {
+        e2ee: Some(E2eeOptions {
+            encryption_type: EncryptionType::Gcm,
+            key_provider,
+        }),
+        ..Default::default()
+    }
+}
+
+/// Mints LiveKit access tokens, e.g. locally with `livekit_api::access_token` or by requesting
+/// one from the backend the device is registered with. Closures returning a token implement it.
+pub trait TokenProvider: Send + Sync {
+    fn token(&self) -> Result<String, LKParticipantError>;
+}
+
+impl<F> TokenProvider for F
+where
+    F: Fn() -> Result<String, LKParticipantError> + Send + Sync,
+{
+    fn token(&self) -> Result<String, LKParticipantError> {
+        self()
+    }
+}
+
+/// Connects to a room with a freshly minted token.
+///
+/// A token only has to be valid when connecting: while connected, the server keeps refreshing
+/// it and the SDK resumes with the refreshed one. Once the room is lost
+/// (`RoomEvent::Disconnected`), the original token may have expired (tokens from
+/// `livekit_api` live 6 hours by default), so long-lived publishers should reconnect with
+/// `LKParticipant::reconnect`, which mints a new one.
+pub async fn connect_with_token_provider(
+    url: &str,
+    token_provider: &dyn TokenProvider,
+    options: RoomOptions,
+) -> Result<(Room, mpsc::UnboundedReceiver<RoomEvent>), LKParticipantError> {
+    let token = token_provider.token()?;
+    Ok(Room::connect(url, &token, options).await?)
+}
+
+/// The state of a stream in a `HealthReport`
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamHealth {
+    pub kind: String,
+    pub device_name: Option<String>,
+    pub started: bool,
+    pub running: bool,
+    pub frozen: bool,
+    /// `None` until the stream has negotiated its caps
+    pub negotiated_caps: Option<NegotiatedCaps>,
+    pub stats: StreamStats,
+    /// The last error that failed the pipeline of the stream, see `GstMediaStream::last_error`
+    pub last_error: Option<String>,
+}
+
+/// A serializable snapshot of a publisher, for scraping the liveness of a fleet
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub crate_version: String,
+    pub gstreamer_version: String,
+    pub room_name: String,
+    pub connection_state: String,
+    pub published_tracks: usize,
+    pub streams: Vec<StreamHealth>,
+}
+
+/// A change in the subscribers of a published track, see `LKParticipant::subscriber_events`
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackSubscriberEvent {
+    /// A remote participant subscribed to the track
+    Subscribed { track_sid: String },
+    /// A remote participant unsubscribed from the track
+    Unsubscribed { track_sid: String },
+    /// The track was unpublished, so it has no subscribers anymore
+    Unpublished { track_sid: String },
+}
+
+/// Updates the subscriber counts of the tracks with a subscriber event
+fn count_subscriber_event(counts: &mut HashMap<String, usize>, event: &TrackSubscriberEvent) {
+    match event {
+        TrackSubscriberEvent::Subscribed { track_sid } => {
+            *counts.entry(track_sid.clone()).or_default() += 1;
+        }
+        TrackSubscriberEvent::Unsubscribed { track_sid } => {
+            if let Some(count) = counts.get_mut(track_sid) {
+                *count = count.saturating_sub(1);
+            }
+        }
+        TrackSubscriberEvent::Unpublished { track_sid } => {
+            counts.remove(track_sid);
+        }
+    }
 }
 
 pub struct LKParticipant {
     room: Arc<Room>,
     published_tracks: HashMap<String, TrackHandle>,
+    // LiveKit track sids of the published tracks, mapped to the sids returned by publish_stream
+    livekit_track_sids: Arc<Mutex<HashMap<String, String>>>,
+    subscriber_tx: broadcast::Sender<TrackSubscriberEvent>,
+    // Number of subscribers of the published tracks, by the sids returned by publish_stream
+    subscriber_counts: Arc<Mutex<HashMap<String, usize>>>,
+    subscriber_task: Option<tokio::task::JoinHandle<()>>,
+    normalize_timestamps: bool,
 }
 
 struct TrackHandle {
     track: LocalTrack,
+    publish_options: TrackPublishOptions,
     task: tokio::task::JoinHandle<()>,
+    metadata: Option<serde_json::Value>,
+    source_options: RtcSourceOptions,
+    // Kept to subscribe the frames of the stream again when the track is recreated
+    frames_rx: broadcast::Receiver<Arc<Buffer>>,
+    close_rx: broadcast::Receiver<()>,
+}
+
+/// What the WebRTC source of a track is created with, to recreate it in `republish_all`
+#[derive(Debug, Clone, Copy)]
+enum RtcSourceOptions {
+    Video {
+        width: u32,
+        height: u32,
+        timestamps: FrameTimestamps,
+    },
+    Audio {
+        sample_rate: u32,
+        num_channels: u32,
+    },
+}
+
+impl RtcSourceOptions {
+    /// Creates the source and its track, and spawns the task feeding it the stream's frames
+    fn create_track(
+        &self,
+        track_name: &str,
+        frames_rx: broadcast::Receiver<Arc<Buffer>>,
+        close_rx: broadcast::Receiver<()>,
+    ) -> (LocalTrack, tokio::task::JoinHandle<()>) {
+        match *self {
+            RtcSourceOptions::Video {
+                width,
+                height,
+                timestamps,
+            } => {
+                let rtc_source = NativeVideoSource::new(VideoResolution { width, height });
+                let track = LocalVideoTrack::create_video_track(
+                    track_name,
+                    RtcVideoSource::Native(rtc_source.clone()),
+                );
+                let task = tokio::spawn(LKParticipant::video_track_task(
+                    close_rx, frames_rx, rtc_source, timestamps,
+                ));
+                (LocalTrack::Video(track), task)
+            }
+            RtcSourceOptions::Audio {
+                sample_rate,
+                num_channels,
+            } => {
+                let rtc_source =
+                    NativeAudioSource::new(Default::default(), sample_rate, num_channels, 2000);
+                let track = LocalAudioTrack::create_audio_track(
+                    track_name,
+                    RtcAudioSource::Native(rtc_source.clone()),
+                );
+                let task = tokio::spawn(LKParticipant::audio_track_task(
+                    close_rx, frames_rx, rtc_source,
+                ));
+                (LocalTrack::Audio(track), task)
+            }
+        }
+    }
 }
 
 impl LKParticipant {
     pub fn new(room: Arc<Room>) -> Self {
+        let (subscriber_tx, _) = broadcast::channel(16);
         Self {
             room,
             published_tracks: HashMap::new(),
+            livekit_track_sids: Arc::new(Mutex::new(HashMap::new())),
+            subscriber_tx,
+            subscriber_counts: Arc::new(Mutex::new(HashMap::new())),
+            subscriber_task: None,
+            normalize_timestamps: false,
+        }
+    }
+
+    /// Rewrites the timestamps of video tracks published from now on so that they increase
+    /// by at least one frame interval of the stream framerate, and at most ten after a jump
+    /// ahead. This avoids the timestamp jumps subscribers see when a resumed pipeline delivers
+    /// a burst of frames with stale timestamps, or continues with timestamps far ahead, at the
+    /// cost of the timestamps no longer being the true capture times.
+    /// Off by default.
+    pub fn set_normalize_timestamps(&mut self, normalize_timestamps: bool) {
+        self.normalize_timestamps = normalize_timestamps;
+    }
+
+    fn frame_timestamps(&self, offset_us: i64, framerate: i32) -> FrameTimestamps {
+        FrameTimestamps {
+            offset_us,
+            pacing_interval_us: (self.normalize_timestamps && framerate > 0)
+                .then(|| 1_000_000 / framerate as i64),
+            last_us: None,
         }
     }
 
+    /// Creates a participant whose published tracks are end-to-end encrypted. The room must
+    /// have been connected with E2EE options, see `e2ee_room_options`.
+    pub fn with_e2ee(room: Arc<Room>) -> Result<Self, LKParticipantError> {
+        let e2ee_manager = room.e2ee_manager();
+        if e2ee_manager.key_provider().is_none() {
+            return Err(LKParticipantError::StreamingError(
+                "Room was not connected with E2EE options".to_string(),
+            ));
+        }
+        e2ee_manager.set_enabled(true);
+        Ok(Self::new(room))
+    }
+
+    /// Publishes a started (or starts and publishes) stream as a track and returns its handle.
+    /// The track is named after the device (and channel) unless `track_name` is given, and
+    /// publishing fails if this participant already publishes a track with that name, see
+    /// `host_scoped_name`.
+    ///
+    /// If the publish options carry `track_metadata`, or an audio stream has a selected channel,
+    /// an entry for the track is added to the participant metadata as
+    /// `{"tracks": {"<track sid>": {"name", "metadata", "channel"}}}`, keeping the other keys of
+    /// the participant metadata. If that update fails, the track is unpublished again and the
+    /// error returned. LiveKit servers cap participant metadata (64 KB by default, configurable with
+    /// `limit.max_metadata_size`), so keep per-track metadata small.
     pub async fn publish_stream(
         &mut self,
         stream: &mut GstMediaStream,
@@ -57,105 +586,522 @@ impl LKParticipant {
         // This unwrap is safe because we know the stream has started
         let (frames_rx, close_rx) = stream.subscribe().unwrap();
         let details = stream.details().unwrap();
-        let track_name = track_name.unwrap_or(stream.get_device_name().unwrap());
-
-        match details {
-            PublishOptions::Video(details) => {
-                let rtc_source = NativeVideoSource::new(VideoResolution {
-                    width: details.width as u32,
-                    height: details.height as u32,
-                });
+        let track_name = track_name.unwrap_or_else(|| {
+            let device_name = stream.get_device_name().unwrap();
+            // Channels of one device published separately, see `AudioMode`, need distinct names
+            match &details {
+                PublishOptions::Audio(details) => match details.selected_channel {
+                    Some(channel) => format!("{} - Channel {}", device_name, channel),
+                    None => device_name,
+                },
+                _ => device_name,
+            }
+        });
+        self.check_track_name_available(&track_name)?;
+        // Streams on a shared clock publish clock time, so that frames captured on several
+        // machines synced to the same (e.g. PTP) clock can be aligned
+        let timestamp_offset_us = stream
+            .shared_clock()
+            .map_or(0, |shared_clock| shared_clock.base_time.useconds() as i64);
+        let metadata = track_metadata_entry(
+            &track_name,
+            details.track_metadata(),
+            match &details {
+                PublishOptions::Audio(details) => details.selected_channel,
+                _ => None,
+            },
+        );
 
-                let track = LocalVideoTrack::create_video_track(
+        // Sources are created with what the device actually negotiated when the stream is
+        // already playing, e.g. a camera that silently picked another size
+        let negotiated = stream.negotiated_caps();
+        let settings = match details {
+            PublishOptions::Video(details) => TrackSettings::Video {
+                source: TrackSource::Camera,
+                preferred_codec: details.preferred_codec,
+                width: details.width,
+                height: details.height,
+                framerate: details.published_framerate(),
+            },
+            PublishOptions::Image(details) => TrackSettings::Video {
+                source: TrackSource::Camera,
+                preferred_codec: None,
+                width: details.width,
+                height: details.height,
+                framerate: details.framerate,
+            },
+            PublishOptions::DeckLink(details) => TrackSettings::Video {
+                source: TrackSource::Camera,
+                preferred_codec: None,
+                width: details.width,
+                height: details.height,
+                framerate: details.framerate,
+            },
+            PublishOptions::Fd(details) => TrackSettings::Video {
+                source: TrackSource::Camera,
+                preferred_codec: None,
+                width: details.width,
+                height: details.height,
+                framerate: details.framerate,
+            },
+            PublishOptions::Rtsp(details) => TrackSettings::Video {
+                source: TrackSource::Camera,
+                preferred_codec: None,
+                width: details.width,
+                height: details.height,
+                framerate: details.framerate,
+            },
+            PublishOptions::Screen(details) => TrackSettings::Video {
+                source: TrackSource::Screenshare,
+                preferred_codec: details.preferred_codec,
+                width: details.width,
+                height: details.height,
+                framerate: details.framerate,
+            },
+            PublishOptions::Audio(details) => TrackSettings::Audio {
+                sample_rate: negotiated
+                    .as_ref()
+                    .and_then(|caps| caps.rate)
+                    .unwrap_or(details.framerate),
+                num_channels: negotiated
+                    .as_ref()
+                    .and_then(|caps| caps.channels)
+                    .unwrap_or(details.published_channels()),
+            },
+            PublishOptions::DeckLinkAudio(details) => TrackSettings::Audio {
+                sample_rate: DECKLINK_AUDIO_RATE,
+                num_channels: details.channels,
+            },
+        };
+        let track_sid = match settings {
+            TrackSettings::Video {
+                source,
+                preferred_codec,
+                width,
+                height,
+                framerate,
+            } => {
+                self.publish_video_track(
                     &track_name,
-                    RtcVideoSource::Native(rtc_source.clone()),
-                );
-
-                let track_sid = random_string("video-track");
-
-                let task = tokio::spawn(Self::video_track_task(
+                    source_resolution(negotiated.as_ref(), width, height),
+                    video_publish_options(source, preferred_codec)?,
+                    self.frame_timestamps(timestamp_offset_us, framerate),
+                    frames_rx,
                     close_rx,
+                )
+                .await
+            }
+            TrackSettings::Audio {
+                sample_rate,
+                num_channels,
+            } => {
+                self.publish_audio_track(
+                    &track_name,
+                    sample_rate as u32,
+                    num_channels as u32,
                     frames_rx,
-                    rtc_source.clone(),
-                ));
-
-                self.room
-                    .local_participant()
-                    .publish_track(
-                        LocalTrack::Video(track.clone()),
-                        TrackPublishOptions {
-                            source: TrackSource::Camera,
-                            ..Default::default()
-                        },
-                    )
-                    .await?;
-
-                self.published_tracks.insert(
-                    track_sid.clone(),
-                    TrackHandle {
-                        track: LocalTrack::Video(track),
-                        task,
-                    },
-                );
+                    close_rx,
+                )
+                .await
+            }
+        }?;
 
-                Ok(track_sid)
+        if let Some(metadata) = metadata {
+            if let Some(handle) = self.published_tracks.get_mut(&track_sid) {
+                handle.metadata = Some(metadata);
             }
-            PublishOptions::Audio(details) => {
-                let rtc_source =
-                    NativeAudioSource::new(Default::default(), details.framerate as u32, 1, 2000);
+            if let Err(e) = self.sync_track_metadata().await {
+                if let Err(unpublish_error) = self.unpublish_track(&track_sid).await {
+                    log::warn!(
+                        "Failed to unpublish track {} after its metadata failed: {}",
+                        track_name,
+                        unpublish_error
+                    );
+                }
+                return Err(e);
+            }
+        }
 
-                let track = LocalAudioTrack::create_audio_track(
-                    &track_name,
-                    RtcAudioSource::Native(rtc_source.clone()),
-                );
+        Ok(track_sid)
+    }
 
-                let track_sid = random_string("audio-track");
+    /// Fails if this participant already publishes a track named `track_name`. Tracks of other
+    /// participants may share names, e.g. the same camera model on separate machines, since
+    /// subscribers tell them apart by participant identity.
+    fn check_track_name_available(&self, track_name: &str) -> Result<(), LKParticipantError> {
+        if self
+            .published_tracks
+            .values()
+            .any(|handle| handle.track.name() == track_name)
+            || self
+                .room
+                .local_participant()
+                .track_publications()
+                .values()
+                .any(|publication| publication.name() == track_name)
+        {
+            return Err(LKParticipantError::StreamingError(format!(
+                "Track {} is already published by this participant",
+                track_name
+            )));
+        }
+        Ok(())
+    }
 
-                let task = tokio::spawn(Self::audio_track_task(
-                    close_rx,
-                    frames_rx,
-                    rtc_source.clone(),
-                ));
+    /// Returns the error to surface for the reason of a `RoomEvent::Disconnected`, if it is not
+    /// a regular disconnection. A `DuplicateIdentity` reason means another process joined with
+    /// the identity of this participant and took its place in the room.
+    pub fn disconnect_error(&self, reason: DisconnectReason) -> Option<LKParticipantError> {
+        match reason {
+            DisconnectReason::DuplicateIdentity => Some(LKParticipantError::DuplicateIdentity(
+                String::from(self.room.local_participant().identity()),
+            )),
+            _ => None,
+        }
+    }
 
-                self.room
-                    .local_participant()
-                    .publish_track(
-                        LocalTrack::Audio(track.clone()),
-                        TrackPublishOptions {
-                            source: TrackSource::Microphone,
-                            ..Default::default()
-                        },
-                    )
-                    .await?;
-
-                self.published_tracks.insert(
-                    track_sid.clone(),
-                    TrackHandle {
-                        track: LocalTrack::Audio(track),
-                        task,
-                    },
-                );
+    /// Publishes the metadata of all tracks that have some under the `"tracks"` key of the
+    /// participant metadata, see `merge_track_metadata`
+    async fn sync_track_metadata(&self) -> Result<(), LKParticipantError> {
+        let tracks: serde_json::Map<String, serde_json::Value> = self
+            .published_tracks
+            .values()
+            .filter_map(|handle| {
+                let metadata = handle.metadata.clone()?;
+                Some((String::from(handle.track.sid()), metadata))
+            })
+            .collect();
+        let local_participant = self.room.local_participant();
+        let metadata = merge_track_metadata(&local_participant.metadata(), tracks)?;
+        local_participant.set_metadata(metadata).await?;
+        Ok(())
+    }
+
+    async fn publish_audio_track(
+        &mut self,
+        track_name: &str,
+        sample_rate: u32,
+        num_channels: u32,
+        frames_rx: broadcast::Receiver<Arc<Buffer>>,
+        close_rx: broadcast::Receiver<()>,
+    ) -> Result<String, LKParticipantError> {
+        let publish_options = TrackPublishOptions {
+            source: TrackSource::Microphone,
+            ..Default::default()
+        };
+        self.publish_track(
+            track_name,
+            random_string("audio-track"),
+            RtcSourceOptions::Audio {
+                sample_rate,
+                num_channels,
+            },
+            publish_options,
+            frames_rx,
+            close_rx,
+        )
+        .await
+    }
+
+    async fn publish_video_track(
+        &mut self,
+        track_name: &str,
+        resolution: VideoResolution,
+        publish_options: TrackPublishOptions,
+        timestamps: FrameTimestamps,
+        frames_rx: broadcast::Receiver<Arc<Buffer>>,
+        close_rx: broadcast::Receiver<()>,
+    ) -> Result<String, LKParticipantError> {
+        self.publish_track(
+            track_name,
+            random_string("video-track"),
+            RtcSourceOptions::Video {
+                width: resolution.width,
+                height: resolution.height,
+                timestamps,
+            },
+            publish_options,
+            frames_rx,
+            close_rx,
+        )
+        .await
+    }
+
+    async fn publish_track(
+        &mut self,
+        track_name: &str,
+        track_sid: String,
+        source_options: RtcSourceOptions,
+        publish_options: TrackPublishOptions,
+        frames_rx: broadcast::Receiver<Arc<Buffer>>,
+        close_rx: broadcast::Receiver<()>,
+    ) -> Result<String, LKParticipantError> {
+        let (track, task) = source_options.create_track(
+            track_name,
+            frames_rx.resubscribe(),
+            close_rx.resubscribe(),
+        );
+
+        self.room
+            .local_participant()
+            .publish_track(track.clone(), publish_options.clone())
+            .await?;
 
-                Ok(track_sid)
+        self.insert_track(
+            &track_sid,
+            TrackHandle {
+                track,
+                publish_options,
+                task,
+                metadata: None,
+                source_options,
+                frames_rx,
+                close_rx,
+            },
+        );
+
+        Ok(track_sid)
+    }
+
+    /// Republishes all published tracks to `room`, e.g. a room connected again after the
+    /// previous one was lost. The WebRTC sources and tracks are recreated, as those of a
+    /// dropped room may be dead, while the captured frames keep coming from the still running
+    /// streams.
+    ///
+    /// Every track is attempted even if some fail to publish, which returns a
+    /// `RepublishError` listing the names of the tracks that recovered and those that did
+    /// not, and whether their track metadata failed to sync. Failed tracks stay in this
+    /// participant, so calling this again retries them.
+    pub async fn republish_all(&mut self, room: Arc<Room>) -> Result<(), LKParticipantError> {
+        self.room = room;
+
+        // Subscriber events follow the events of the previous room
+        self.subscriber_counts.lock().unwrap().clear();
+        if let Some(task) = self.subscriber_task.take() {
+            task.abort();
+            let _ = self.subscriber_events();
+        }
+
+        self.livekit_track_sids.lock().unwrap().clear();
+        let mut recovered = vec![];
+        let mut failed = vec![];
+        for (track_sid, handle) in self.published_tracks.iter_mut() {
+            handle.task.abort();
+            let track_name = handle.track.name();
+            let (track, task) = handle.source_options.create_track(
+                &track_name,
+                handle.frames_rx.resubscribe(),
+                handle.close_rx.resubscribe(),
+            );
+            handle.track = track;
+            handle.task = task;
+
+            match self
+                .room
+                .local_participant()
+                .publish_track(handle.track.clone(), handle.publish_options.clone())
+                .await
+            {
+                Ok(_) => {
+                    self.livekit_track_sids
+                        .lock()
+                        .unwrap()
+                        .insert(String::from(handle.track.sid()), track_sid.clone());
+                    recovered.push(track_name);
+                }
+                Err(e) => failed.push(format!("{} ({})", track_name, e)),
             }
         }
+
+        let mut metadata_error = None;
+        if !recovered.is_empty()
+            && self
+                .published_tracks
+                .values()
+                .any(|handle| handle.metadata.is_some())
+        {
+            metadata_error = self
+                .sync_track_metadata()
+                .await
+                .err()
+                .map(|e| e.to_string());
+        }
+
+        if !failed.is_empty() || metadata_error.is_some() {
+            return Err(LKParticipantError::RepublishError {
+                recovered,
+                failed,
+                metadata_error,
+            });
+        }
+        Ok(())
+    }
+
+    /// Connects to a new room with a freshly minted token and republishes all published tracks
+    /// to it with `republish_all`, keeping their capture running. Call this after the room is
+    /// disconnected; the returned receiver replaces the event receiver of the previous room.
+    /// Every track is attempted, and those that fail are reported in a `RepublishError`.
+    pub async fn reconnect(
+        &mut self,
+        url: &str,
+        token_provider: &dyn TokenProvider,
+        options: RoomOptions,
+    ) -> Result<mpsc::UnboundedReceiver<RoomEvent>, LKParticipantError> {
+        let (room, room_events) = connect_with_token_provider(url, token_provider, options).await?;
+        self.republish_all(Arc::new(room)).await?;
+        Ok(room_events)
+    }
+
+    /// Returns a snapshot of the room connection, the published tracks and the given streams
+    pub fn health_report<'a>(
+        &self,
+        streams: impl IntoIterator<Item = &'a GstMediaStream>,
+    ) -> HealthReport {
+        HealthReport {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            gstreamer_version: gstreamer::version_string().to_string(),
+            room_name: self.room.name(),
+            connection_state: format!("{:?}", self.room.connection_state()),
+            published_tracks: self.published_tracks.len(),
+            streams: streams
+                .into_iter()
+                .map(|stream| StreamHealth {
+                    kind: stream.kind().to_string(),
+                    device_name: stream.get_device_name(),
+                    started: stream.has_started(),
+                    running: stream.is_running(),
+                    frozen: stream.is_frozen(),
+                    negotiated_caps: stream.negotiated_caps(),
+                    stats: stream.stats(),
+                    last_error: stream.last_error(),
+                })
+                .collect(),
+        }
+    }
+
+    fn insert_track(&mut self, track_sid: &str, handle: TrackHandle) {
+        self.livekit_track_sids
+            .lock()
+            .unwrap()
+            .insert(String::from(handle.track.sid()), track_sid.to_string());
+        self.published_tracks.insert(track_sid.to_string(), handle);
+    }
+
+    /// Returns a receiver of subscription changes of the published tracks, with the track sids
+    /// returned by `publish_stream`. Users can use it to e.g. pause capture while nobody watches.
+    ///
+    /// Subscriptions are derived from the room events, which also keep `subscriber_count` up
+    /// to date from the first call on. `Unpublished` marks the end of all of a track's
+    /// subscribers.
+    pub fn subscriber_events(&mut self) -> broadcast::Receiver<TrackSubscriberEvent> {
+        if self.subscriber_task.is_none() {
+            let mut room_events = self.room.subscribe();
+            let subscriber_tx = self.subscriber_tx.clone();
+            let subscriber_counts = self.subscriber_counts.clone();
+            let livekit_track_sids = self.livekit_track_sids.clone();
+            self.subscriber_task = Some(tokio::spawn(async move {
+                while let Some(event) = room_events.recv().await {
+                    let (livekit_sid, subscribed) = match event {
+                        RoomEvent::LocalTrackSubscribed { track } => (track.sid(), true),
+                        RoomEvent::TrackUnsubscribed { publication, .. } => {
+                            (publication.sid(), false)
+                        }
+                        _ => continue,
+                    };
+                    let track_sid = livekit_track_sids
+                        .lock()
+                        .unwrap()
+                        .get(&String::from(livekit_sid))
+                        .cloned();
+                    if let Some(track_sid) = track_sid {
+                        let event = if subscribed {
+                            TrackSubscriberEvent::Subscribed { track_sid }
+                        } else {
+                            TrackSubscriberEvent::Unsubscribed { track_sid }
+                        };
+                        count_subscriber_event(&mut subscriber_counts.lock().unwrap(), &event);
+                        let _ = subscriber_tx.send(event);
+                    }
+                }
+            }));
+        }
+        self.subscriber_tx.subscribe()
+    }
+
+    /// Returns the number of remote participants subscribed to a track, by the sid returned by
+    /// `publish_stream`. Subscriptions are only counted once `subscriber_events` was called.
+    pub fn subscriber_count(&self, track_sid: &str) -> usize {
+        self.subscriber_counts
+            .lock()
+            .unwrap()
+            .get(track_sid)
+            .copied()
+            .unwrap_or(0)
     }
 
     pub async fn unpublish_track(&mut self, track_sid: &str) -> Result<(), LKParticipantError> {
-        if let Some(handle) = self.published_tracks.get(track_sid) {
+        if let Some(handle) = self.published_tracks.remove(track_sid) {
+            handle.task.abort();
+            self.livekit_track_sids
+                .lock()
+                .unwrap()
+                .remove(&String::from(handle.track.sid()));
+            let event = TrackSubscriberEvent::Unpublished {
+                track_sid: track_sid.to_string(),
+            };
+            count_subscriber_event(&mut self.subscriber_counts.lock().unwrap(), &event);
+            let _ = self.subscriber_tx.send(event);
             self.room
                 .local_participant()
                 .unpublish_track(&handle.track.sid())
                 .await?;
-            handle.task.abort();
+            if handle.metadata.is_some() {
+                self.sync_track_metadata().await?;
+            }
         }
         Ok(())
     }
 
+    /// Unpublishes every track, stops the given streams and closes the room.
+    ///
+    /// This is meant to be awaited on application exit so that devices are released and
+    /// tracks do not linger server-side. It is idempotent: already stopped streams, already
+    /// unpublished tracks and an already closed room are ignored. All steps are attempted
+    /// even if one fails, and the first error is returned.
+    pub async fn shutdown_all<'a>(
+        &mut self,
+        streams: impl IntoIterator<Item = &'a mut GstMediaStream>,
+    ) -> Result<(), LKParticipantError> {
+        let mut first_error = None;
+
+        let track_sids: Vec<String> = self.published_tracks.keys().cloned().collect();
+        for track_sid in track_sids {
+            if let Err(e) = self.unpublish_track(&track_sid).await {
+                first_error.get_or_insert(e);
+            }
+        }
+
+        for stream in streams {
+            if let Err(e) = stream.stop().await {
+                first_error.get_or_insert(e.into());
+            }
+        }
+
+        match self.room.close().await {
+            Ok(()) | Err(RoomError::AlreadyClosed) => {}
+            Err(e) => {
+                first_error.get_or_insert(e.into());
+            }
+        }
+
+        first_error.map_or(Ok(()), Err)
+    }
+
     async fn video_track_task(
         mut close_rx: broadcast::Receiver<()>,
         mut frames_rx: broadcast::Receiver<Arc<Buffer>>,
         rtc_source: NativeVideoSource,
+        mut timestamps: FrameTimestamps,
     ) {
         loop {
             tokio::select! {
@@ -166,21 +1112,58 @@ impl LKParticipant {
                     if let Ok(frame) = frame {
                         let map = frame.map_readable().unwrap();
                         let data = map.as_slice();
-                        let timestamp_us = frame.pts().unwrap_or_default().useconds() as i64;
-                        let res = rtc_source.video_resolution();
-                        let width = res.width;
-                        let height = res.height;
+                        let timestamp_us =
+                            timestamps.next(frame.pts().unwrap_or_default().useconds() as i64);
+                        // Frames carry their own size, which can differ from the resolution
+                        // the source was created with, e.g. after a shared window is resized.
+                        // That resolution is fixed but only a hint, WebRTC takes frames of
+                        // any size.
+                        let (width, height) = frame
+                            .meta::<gstreamer_video::VideoMeta>()
+                            .map(|meta| (meta.width(), meta.height()))
+                            .unwrap_or_else(|| {
+                                let res = rtc_source.video_resolution();
+                                (res.width, res.height)
+                            });
                         let mut wrtc_video_buffer = I420Buffer::new(width, height);
+                        let (stride_y, stride_u, stride_v) = wrtc_video_buffer.strides();
                         let (data_y, data_u, data_v) = wrtc_video_buffer.data_mut();
 
-                        let y_plane_size = (width * height) as usize;
-                        let uv_plane_size = (width * height / 4) as usize;
-
-                        data_y.copy_from_slice(&data[0..y_plane_size]);
-                        data_u.copy_from_slice(&data[y_plane_size..y_plane_size + uv_plane_size]);
-                        data_v.copy_from_slice(
-                            &data[y_plane_size + uv_plane_size..y_plane_size + 2 * uv_plane_size],
+                        // Rows are copied one by one as GStreamer pads them to its own
+                        // stride, e.g. to a multiple of 4 bytes for odd widths
+                        let Some((offsets, strides)) = i420_layout(&frame, width, height) else {
+                            continue;
+                        };
+                        let (chroma_width, chroma_height) =
+                            (width.div_ceil(2) as usize, height.div_ceil(2) as usize);
+                        let copied = copy_plane(
+                            data,
+                            offsets[0],
+                            strides[0],
+                            data_y,
+                            stride_y as usize,
+                            width as usize,
+                            height as usize,
+                        ) && copy_plane(
+                            data,
+                            offsets[1],
+                            strides[1],
+                            data_u,
+                            stride_u as usize,
+                            chroma_width,
+                            chroma_height,
+                        ) && copy_plane(
+                            data,
+                            offsets[2],
+                            strides[2],
+                            data_v,
+                            stride_v as usize,
+                            chroma_width,
+                            chroma_height,
                         );
+                        if !copied {
+                            continue;
+                        }
 
                         let video_frame = VideoFrame {
                             buffer: wrtc_video_buffer,
@@ -210,7 +1193,16 @@ impl LKParticipant {
                             let audio_data: &[i16] = unsafe {
                                 std::slice::from_raw_parts(map.as_ptr() as *const i16, map.size() / 2)
                             };
-                            let samples_per_channel = audio_data.len() as u32 / rtc_source.num_channels();
+                            let Some(samples_per_channel) =
+                                samples_per_channel(audio_data.len(), rtc_source.num_channels())
+                            else {
+                                log::warn!(
+                                    "Dropping audio buffer of {} samples, not a whole number of {} channel frames",
+                                    audio_data.len(),
+                                    rtc_source.num_channels()
+                                );
+                                continue;
+                            };
                             let audio_frame = AudioFrame {
                                 data: Cow::Borrowed(audio_data),
                                 sample_rate: rtc_source.sample_rate(),
@@ -224,3 +1216,218 @@ impl LKParticipant {
         }
     }
 }
+
+impl Drop for LKParticipant {
+    fn drop(&mut self) {
+        if let Some(task) = self.subscriber_task.take() {
+            task.abort();
+        }
+    }
+}
+
+mod tests {
+    #[cfg(test)]
+    use super::*;
+
+    #[test]
+    fn test_scoped_name() {
+        assert_eq!(
+            scoped_name("camera", "lab-pc", "/dev/video0"),
+            "camera-lab-pc-dev-video0"
+        );
+        assert_eq!(
+            scoped_name("mic", "Lab PC.local", "front:UMC1820"),
+            "mic-Lab-PC-local-front-UMC1820"
+        );
+        assert_eq!(scoped_name("", "lab-pc", "hw:1"), "lab-pc-hw-1");
+    }
+
+    #[test]
+    fn test_count_subscriber_event() {
+        let mut counts = HashMap::new();
+        let track_sid = "track".to_string();
+        for event in [
+            TrackSubscriberEvent::Subscribed {
+                track_sid: track_sid.clone(),
+            },
+            TrackSubscriberEvent::Subscribed {
+                track_sid: track_sid.clone(),
+            },
+            TrackSubscriberEvent::Unsubscribed {
+                track_sid: track_sid.clone(),
+            },
+        ] {
+            count_subscriber_event(&mut counts, &event);
+        }
+        assert_eq!(counts.get(&track_sid), Some(&1));
+
+        count_subscriber_event(
+            &mut counts,
+            &TrackSubscriberEvent::Unpublished {
+                track_sid: track_sid.clone(),
+            },
+        );
+        assert_eq!(counts.get(&track_sid), None);
+    }
+
+    #[test]
+    fn test_source_resolution() {
+        let negotiated = NegotiatedCaps {
+            media_type: "video/x-raw".to_string(),
+            format: Some("I420".to_string()),
+            width: Some(640),
+            height: Some(480),
+            framerate: Some((30, 1)),
+            channels: None,
+            rate: None,
+        };
+        let resolution = source_resolution(Some(&negotiated), 1280, 720);
+        assert_eq!((resolution.width, resolution.height), (640, 480));
+        let resolution = source_resolution(None, 1280, 720);
+        assert_eq!((resolution.width, resolution.height), (1280, 720));
+    }
+
+    #[test]
+    fn test_copy_i420_planes_with_stride() {
+        gstreamer::init().unwrap();
+        for (width, height) in [(1280, 721), (641, 481)] {
+            let info = gstreamer_video::VideoInfo::builder(
+                gstreamer_video::VideoFormat::I420,
+                width,
+                height,
+            )
+            .build()
+            .unwrap();
+            // Padding bytes are 0xff, pixels the index of their plane
+            let mut data = vec![0xffu8; info.size()];
+            let plane_sizes = [
+                (width, height),
+                (width.div_ceil(2), height.div_ceil(2)),
+                (width.div_ceil(2), height.div_ceil(2)),
+            ];
+            for (plane, (plane_width, plane_height)) in plane_sizes.iter().enumerate() {
+                for row in 0..*plane_height as usize {
+                    let start = info.offset()[plane] + row * info.stride()[plane] as usize;
+                    data[start..start + *plane_width as usize].fill(plane as u8);
+                }
+            }
+            let mut buffer = gstreamer::Buffer::from_slice(data.clone());
+            gstreamer_video::VideoMeta::add_full(
+                buffer.get_mut().unwrap(),
+                gstreamer_video::VideoFrameFlags::empty(),
+                info.format(),
+                width,
+                height,
+                info.offset(),
+                info.stride(),
+            )
+            .unwrap();
+            // Without a video meta, the layout is the default one of GStreamer
+            let buffer_without_meta = gstreamer::Buffer::from_slice(data.clone());
+
+            for buffer in [&buffer, &buffer_without_meta] {
+                let (offsets, strides) = i420_layout(buffer, width, height).unwrap();
+                for (plane, (plane_width, plane_height)) in plane_sizes.iter().enumerate() {
+                    let (plane_width, plane_height) =
+                        (*plane_width as usize, *plane_height as usize);
+                    let mut dst = vec![0xaau8; plane_width * plane_height];
+                    assert!(copy_plane(
+                        &data,
+                        offsets[plane],
+                        strides[plane],
+                        &mut dst,
+                        plane_width,
+                        plane_width,
+                        plane_height,
+                    ));
+                    assert!(dst.iter().all(|byte| *byte == plane as u8));
+                }
+            }
+        }
+        assert!(i420_layout(&gstreamer::Buffer::new(), 0, 0).is_none());
+
+        let mut dst = vec![0u8; 4];
+        assert!(!copy_plane(&[0u8; 5], 0, 4, &mut dst, 2, 2, 2));
+        assert!(copy_plane(&[0u8; 6], 0, 4, &mut dst, 2, 2, 2));
+    }
+
+    #[test]
+    fn test_frame_timestamps_normalized() {
+        let mut timestamps = FrameTimestamps {
+            offset_us: 0,
+            pacing_interval_us: Some(33_333),
+            last_us: None,
+        };
+        assert_eq!(timestamps.next(1_000_000), 1_000_000);
+        // A burst of stale frames is paced at the frame interval
+        assert_eq!(timestamps.next(900_000), 1_033_333);
+        assert_eq!(timestamps.next(900_010), 1_066_666);
+        // Small gaps, e.g. of dropped frames, are kept
+        assert_eq!(timestamps.next(1_166_666), 1_166_666);
+        // A jump ahead is closed to a frame interval, and the following frames continue from it
+        assert_eq!(timestamps.next(5_000_000), 1_199_999);
+        assert_eq!(timestamps.next(5_033_333), 1_233_332);
+        // A jump back is paced as well
+        assert_eq!(timestamps.next(100_000), 1_266_665);
+
+        let mut timestamps = FrameTimestamps {
+            offset_us: 10,
+            pacing_interval_us: None,
+            last_us: None,
+        };
+        assert_eq!(timestamps.next(1_000_000), 1_000_010);
+        assert_eq!(timestamps.next(900_000), 900_010);
+    }
+
+    #[test]
+    fn test_samples_per_channel() {
+        assert_eq!(samples_per_channel(960, 2), Some(480));
+        assert_eq!(samples_per_channel(480, 1), Some(480));
+        assert_eq!(samples_per_channel(961, 2), None);
+        assert_eq!(samples_per_channel(960, 0), None);
+    }
+
+    #[test]
+    fn test_track_metadata_entry() {
+        assert!(track_metadata_entry("mic", None, None).is_none());
+
+        let entry = track_metadata_entry("mic", Some(r#"{"device_id": "umc"}"#), Some(2));
+        assert_eq!(
+            entry,
+            Some(serde_json::json!({
+                "name": "mic",
+                "metadata": {"device_id": "umc"},
+                "channel": 2,
+            }))
+        );
+
+        let entry = track_metadata_entry("cam", Some("session-1"), None).unwrap();
+        assert_eq!(entry["metadata"], "session-1");
+        assert!(entry["channel"].is_null());
+    }
+
+    #[test]
+    fn test_merge_track_metadata() {
+        let mut tracks = serde_json::Map::new();
+        tracks.insert("TR_1".to_string(), serde_json::json!({"name": "mic"}));
+
+        let merged = merge_track_metadata("", tracks.clone()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&merged).unwrap(),
+            serde_json::json!({"tracks": {"TR_1": {"name": "mic"}}})
+        );
+
+        let merged = merge_track_metadata(
+            r#"{"role": "observer", "tracks": {"TR_0": {"name": "cam"}}}"#,
+            tracks.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&merged).unwrap(),
+            serde_json::json!({"role": "observer", "tracks": {"TR_1": {"name": "mic"}}})
+        );
+
+        assert!(merge_track_metadata("observer", tracks.clone()).is_err());
+        assert!(merge_track_metadata("[1, 2]", tracks).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use std::fs::OpenOptions;
+use std::os::fd::AsRawFd;
+
+use crate::media_device::GStreamerError;
+
+// ioctl request codes from linux/videodev2.h
+const VIDIOC_ENUMINPUT: u64 = 0xC050_561A;
+const VIDIOC_S_INPUT: u64 = 0xC004_5627;
+
+#[repr(C)]
+#[allow(dead_code)]
+struct V4l2Input {
+    index: u32,
+    name: [u8; 32],
+    input_type: u32,
+    audioset: u32,
+    tuner: u32,
+    std: u64,
+    status: u32,
+    capabilities: u32,
+    reserved: [u32; 3],
+}
+
+/// An input (e.g. composite or S-video) exposed by a v4l2 capture device
+#[derive(Debug, Clone)]
+pub struct VideoInput {
+    pub index: u32,
+    pub name: String,
+}
+
+pub fn list_inputs(device_path: &str) -> Result<Vec<VideoInput>, GStreamerError> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(device_path)
+        .map_err(|e| {
+            GStreamerError::DeviceError(format!("Failed to open {}: {}", device_path, e))
+        })?;
+
+    let mut inputs = vec![];
+    for index in 0.. {
+        let mut input = V4l2Input {
+            index,
+            name: [0; 32],
+            input_type: 0,
+            audioset: 0,
+            tuner: 0,
+            std: 0,
+            status: 0,
+            capabilities: 0,
+            reserved: [0; 3],
+        };
+        let res = unsafe {
+            libc::ioctl(
+                file.as_raw_fd(),
+                VIDIOC_ENUMINPUT as _,
+                &mut input as *mut V4l2Input,
+            )
+        };
+        if res < 0 {
+            break;
+        }
+        let name_len = input.name.iter().position(|&b| b == 0).unwrap_or(32);
+        inputs.push(VideoInput {
+            index,
+            name: String::from_utf8_lossy(&input.name[..name_len]).into_owned(),
+        });
+    }
+    Ok(inputs)
+}
+
+pub fn set_input(device_path: &str, index: u32) -> Result<(), GStreamerError> {
+    let inputs = list_inputs(device_path)?;
+    if !inputs.is_empty() && !inputs.iter().any(|i| i.index == index) {
+        return Err(GStreamerError::DeviceError(format!(
+            "Input {} is not available on {}, available inputs: {:?}",
+            index, device_path, inputs
+        )));
+    }
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(device_path)
+        .map_err(|e| {
+            GStreamerError::DeviceError(format!("Failed to open {}: {}", device_path, e))
+        })?;
+
+    let mut index = index as libc::c_int;
+    let res = unsafe {
+        libc::ioctl(
+            file.as_raw_fd(),
+            VIDIOC_S_INPUT as _,
+            &mut index as *mut libc::c_int,
+        )
+    };
+    if res < 0 {
+        return Err(GStreamerError::DeviceError(format!(
+            "Failed to select input {} on {}: {}",
+            index,
+            device_path,
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
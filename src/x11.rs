@@ -0,0 +1,54 @@
+use crate::media_device::GStreamerError;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, Window};
+
+/// A top level window of the X display that can be captured with `ximagesrc`
+#[derive(Debug, Clone, PartialEq)]
+pub struct XWindow {
+    pub xid: Window,
+    pub title: String,
+}
+
+fn x11_error(err: impl std::fmt::Display) -> GStreamerError {
+    GStreamerError::DeviceError(format!("X11: {}", err))
+}
+
+/// Lists the windows managed by the window manager, as listed in the root window's
+/// `_NET_CLIENT_LIST`. Windows without a title are skipped.
+pub fn list_windows() -> Result<Vec<XWindow>, GStreamerError> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(x11_error)?;
+    let root = conn.setup().roots[screen_num].root;
+    let atom = |name: &[u8]| -> Result<u32, GStreamerError> {
+        Ok(conn
+            .intern_atom(false, name)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?
+            .atom)
+    };
+    let client_list = atom(b"_NET_CLIENT_LIST")?;
+    let net_wm_name = atom(b"_NET_WM_NAME")?;
+    let utf8_string = atom(b"UTF8_STRING")?;
+
+    let property = |window: Window, property: u32, type_: u32| {
+        conn.get_property(false, window, property, type_, 0, u32::MAX)
+            .ok()?
+            .reply()
+            .ok()
+    };
+    let windows = property(root, client_list, AtomEnum::WINDOW.into())
+        .and_then(|reply| reply.value32().map(|windows| windows.collect::<Vec<_>>()))
+        .unwrap_or_default();
+
+    Ok(windows
+        .into_iter()
+        .filter_map(|xid| {
+            let title = property(xid, net_wm_name, utf8_string)
+                .filter(|reply| !reply.value.is_empty())
+                .or_else(|| property(xid, AtomEnum::WM_NAME.into(), AtomEnum::STRING.into()))
+                .map(|reply| String::from_utf8_lossy(&reply.value).into_owned())
+                .filter(|title| !title.is_empty())?;
+            Some(XWindow { xid, title })
+        })
+        .collect())
+}